@@ -1,7 +1,134 @@
+use kiva::tls::ClientConn;
+use std::io;
 use std::io::prelude::*;
+use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio::prelude::*;
 
+/// Returns the `--tls <server-name>` argument when present, e.g.
+/// `--tls kiba.example.com`. Plaintext mode is the default.
+fn parse_tls_arg() -> Option<String> {
+    kiva::argv::flag_value("--tls")
+}
+
+/// Delay before the first reconnect attempt; doubles on each subsequent
+/// failure up to `MAX_BACKOFF`.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// A connection to the server that reconnects itself with exponential
+/// backoff whenever the socket errors out or hits EOF, so transient network
+/// blips and server restarts don't crash the client.
+struct Connection {
+    url: String,
+    tls_server_name: Option<String>,
+    stream: ClientConn,
+}
+
+impl Connection {
+    async fn connect(url: &str, tls_server_name: Option<String>) -> Self {
+        let stream = Self::dial_with_backoff(url, &tls_server_name).await;
+        println!("** Successfully established outbound TCP connection");
+        Connection {
+            url: url.to_string(),
+            tls_server_name,
+            stream,
+        }
+    }
+
+    async fn dial(url: &str, tls_server_name: &Option<String>) -> io::Result<ClientConn> {
+        let tcp = TcpStream::connect(url).await?;
+
+        #[cfg(feature = "tls")]
+        let stream = match tls_server_name {
+            Some(server_name) => {
+                let (connector, name) = kiva::tls::load_client_connector(server_name)?;
+                ClientConn::Tls(connector.connect(name, tcp).await?)
+            }
+            None => ClientConn::Plain(tcp),
+        };
+        #[cfg(not(feature = "tls"))]
+        let stream = {
+            if tls_server_name.is_some() {
+                panic!("--tls requires the `tls` feature to be enabled");
+            }
+            ClientConn::Plain(tcp)
+        };
+
+        Ok(stream)
+    }
+
+    /// Dials `url`, retrying with exponential backoff plus a little jitter
+    /// until it succeeds. Used both for the first connection attempt and
+    /// for reconnects, since a server that isn't up yet and one that just
+    /// dropped the link deserve the same treatment.
+    async fn dial_with_backoff(url: &str, tls_server_name: &Option<String>) -> ClientConn {
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            match Self::dial(url, tls_server_name).await {
+                Ok(stream) => return stream,
+                Err(e) => {
+                    let jittered = backoff + Duration::from_millis(jitter_millis());
+                    println!(
+                        "** Connect to {} failed ({}), retrying in {:?}",
+                        url, e, jittered
+                    );
+                    tokio::time::delay_for(jittered).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    /// Reconnects with exponential backoff, retrying forever since the
+    /// server is expected to come back eventually.
+    async fn reconnect(&mut self) {
+        self.stream = Self::dial_with_backoff(&self.url, &self.tls_server_name).await;
+        println!("** Reconnected to {}", self.url);
+    }
+
+    /// Sends `line` and returns the server's response line, transparently
+    /// reconnecting and replaying the request if the connection has dropped.
+    async fn send(&mut self, line: &str) -> io::Result<String> {
+        loop {
+            match self.try_send(line).await {
+                Ok(resp) => return Ok(resp),
+                Err(e) => {
+                    println!("** Lost connection to {} ({}), reconnecting...", self.url, e);
+                    self.reconnect().await;
+                }
+            }
+        }
+    }
+
+    async fn try_send(&mut self, line: &str) -> io::Result<String> {
+        self.stream
+            .write_all(format!("{}\r\n", line).as_bytes())
+            .await?;
+
+        let mut resp = Vec::new();
+        let mut byte = [0; 1];
+        loop {
+            self.stream.read_exact(&mut byte).await?;
+            if byte[0] == b'\n' && resp.last() == Some(&b'\r') {
+                resp.pop();
+                break;
+            }
+            resp.push(byte[0]);
+        }
+        Ok(String::from_utf8_lossy(&resp).into_owned())
+    }
+}
+
+/// A small pseudo-random jitter in `0..50` milliseconds, derived from the
+/// wall clock so repeated backoffs don't all retry in lockstep.
+fn jitter_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| u64::from(d.subsec_millis()) % 50)
+        .unwrap_or(0)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("==================");
@@ -9,9 +136,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("==================");
 
     let url = "127.0.0.1:6464";
-    let mut stream = TcpStream::connect(url).await?;
-
-    println!("** Successfully established outbound TCP connection");
+    let mut conn = Connection::connect(url, parse_tls_arg()).await;
     println!("** Listening on: {}", url);
 
     loop {
@@ -21,11 +146,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         std::io::stdin()
             .read_line(&mut wbuf)
             .expect("Failed to read input");
-        stream.write_all(wbuf.as_bytes()).await?;
-
-        let mut rbuf = [0; 128];
-        stream.read(&mut rbuf[..]).await?;
 
-        println!("{}", String::from_utf8_lossy(&rbuf));
+        let resp = conn.send(wbuf.trim_end()).await?;
+        println!("{}", resp);
     }
 }
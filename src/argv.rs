@@ -0,0 +1,17 @@
+//! The minimal `--flag value` argv parsing shared by the server and client
+//! binaries, which don't pull in a full CLI-parsing dependency for a
+//! handful of optional flags.
+
+/// Returns the value following `flag` in the process argv, if present.
+pub fn flag_value(flag: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
+/// Reports whether `flag` is present anywhere in the process argv.
+pub fn has_flag(flag: &str) -> bool {
+    std::env::args().any(|a| a == flag)
+}
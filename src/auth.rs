@@ -0,0 +1,56 @@
+//! Authentication backends for the `AUTH` command.
+
+/// Verifies a client-supplied `AUTH` secret.
+///
+/// Kept as a trait so the connection loop never has to know how a secret is
+/// actually checked — today it's a single shared secret, but hashed
+/// credentials or per-user tokens can be swapped in without touching
+/// dispatch.
+pub trait Authenticator: Send + Sync {
+    fn verify(&self, secret: &str) -> bool;
+}
+
+/// Authenticates against a single shared secret, configured once at server
+/// startup (env var or `--auth-secret` flag).
+pub struct StaticSecretAuthenticator {
+    secret: String,
+}
+
+impl StaticSecretAuthenticator {
+    pub fn new(secret: String) -> Self {
+        StaticSecretAuthenticator { secret }
+    }
+}
+
+impl Authenticator for StaticSecretAuthenticator {
+    fn verify(&self, secret: &str) -> bool {
+        constant_time_eq(secret.as_bytes(), self.secret.as_bytes())
+    }
+}
+
+/// Compares two byte strings in time independent of where they first differ,
+/// so a network client can't use response timing to learn the secret one
+/// byte at a time.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    diff == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_secret_authenticator() {
+        let auth = StaticSecretAuthenticator::new("hunter2".to_string());
+        assert!(auth.verify("hunter2"));
+        assert!(!auth.verify("wrong"));
+        assert!(!auth.verify(""));
+    }
+}
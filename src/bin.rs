@@ -1,17 +1,115 @@
-use kiva::{HashStore, Store};
+use kiva::auth::{Authenticator, StaticSecretAuthenticator};
+use kiva::tls::ServerConn;
+use kiva::{HashStore, PersistentStore, Store, Ttl};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use tokio::net::TcpListener;
 use tokio::prelude::*;
 use tokio::sync::{mpsc, oneshot};
 
-#[derive(Debug, PartialEq)]
+/// Hands out a unique id per connection so the manager can tell subscribers
+/// apart in its per-channel fan-out list.
+static NEXT_CONN_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Reads the configured AUTH secret from `KIBA_AUTH_SECRET`, falling back to
+/// an `--auth-secret <secret>` flag. Returns `None` when neither is set, in
+/// which case the server accepts unauthenticated connections.
+fn parse_auth_secret() -> Option<String> {
+    std::env::var("KIBA_AUTH_SECRET")
+        .ok()
+        .or_else(|| kiva::argv::flag_value("--auth-secret"))
+}
+
+/// TLS settings parsed from the `--tls --cert <path> --key <path>` flags.
+struct TlsArgs {
+    cert: std::path::PathBuf,
+    key: std::path::PathBuf,
+}
+
+/// Scans the process argv for `--tls`, returning its accompanying
+/// `--cert`/`--key` paths when present. Plaintext mode is the default.
+fn parse_tls_args() -> Option<TlsArgs> {
+    if !kiva::argv::has_flag("--tls") {
+        return None;
+    }
+    let find = |flag: &str| kiva::argv::flag_value(flag).map(std::path::PathBuf::from);
+    Some(TlsArgs {
+        cert: find("--cert").expect("--tls requires --cert <path>"),
+        key: find("--key").expect("--tls requires --key <path>"),
+    })
+}
+
+#[derive(PartialEq)]
 enum Request {
     Ping,
     Get { key: String },
-    Set { key: String, val: String },
+    Set { key: String, val: String, ex: Option<u64> },
+    Expire { key: String, seconds: u64 },
+    Ttl { key: String },
+    Auth { secret: String },
+    Subscribe { channel: String },
+    Unsubscribe { channel: String },
+    Publish { channel: String, message: String },
     NoOp,
     Invalid { error: String },
 }
 
+/// Hand-rolled so `AUTH <secret>` never ends up in a log line: the derived
+/// impl would print `secret` verbatim, which defeats the point of comparing
+/// it in constant time in the first place.
+impl std::fmt::Debug for Request {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Request::Ping => write!(f, "Ping"),
+            Request::Get { key } => f.debug_struct("Get").field("key", key).finish(),
+            Request::Set { key, val, ex } => f
+                .debug_struct("Set")
+                .field("key", key)
+                .field("val", val)
+                .field("ex", ex)
+                .finish(),
+            Request::Expire { key, seconds } => f
+                .debug_struct("Expire")
+                .field("key", key)
+                .field("seconds", seconds)
+                .finish(),
+            Request::Ttl { key } => f.debug_struct("Ttl").field("key", key).finish(),
+            Request::Auth { .. } => f.debug_struct("Auth").field("secret", &"<redacted>").finish(),
+            Request::Subscribe { channel } => {
+                f.debug_struct("Subscribe").field("channel", channel).finish()
+            }
+            Request::Unsubscribe { channel } => f
+                .debug_struct("Unsubscribe")
+                .field("channel", channel)
+                .finish(),
+            Request::Publish { channel, message } => f
+                .debug_struct("Publish")
+                .field("channel", channel)
+                .field("message", message)
+                .finish(),
+            Request::NoOp => write!(f, "NoOp"),
+            Request::Invalid { error } => f.debug_struct("Invalid").field("error", error).finish(),
+        }
+    }
+}
+
+/// Reports whether `req` must be refused with `-ERR not authenticated` on a
+/// connection that hasn't passed AUTH yet, when the server requires it at
+/// all. Centralized so a future protocol addition can't silently skip the
+/// gate the way `Expire`/`Ttl` originally did.
+fn requires_auth(req: &Request) -> bool {
+    matches!(
+        req,
+        Request::Get { .. }
+            | Request::Set { .. }
+            | Request::Expire { .. }
+            | Request::Ttl { .. }
+            | Request::Subscribe { .. }
+            | Request::Unsubscribe { .. }
+            | Request::Publish { .. }
+    )
+}
+
 #[derive(Debug, PartialEq)]
 struct Response {
     body: String,
@@ -21,6 +119,12 @@ struct Response {
 struct Message {
     req: Request,
     pipe: oneshot::Sender<Response>,
+    /// Identifies the connection issuing `Subscribe`/`Unsubscribe`, so the
+    /// manager can find that connection's entry in the subscribers map.
+    conn_id: u64,
+    /// Present only on `Subscribe`: the half of this connection's mailbox
+    /// the manager should fan published messages into.
+    subscribe_tx: Option<mpsc::Sender<String>>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -28,6 +132,13 @@ enum Token {
     Ping,
     Get,
     Set,
+    Ex,
+    Expire,
+    Ttl,
+    Auth,
+    Subscribe,
+    Unsubscribe,
+    Publish,
     Operand(String),
 }
 
@@ -65,9 +176,9 @@ async fn parse_tokens(tokens: Vec<Token>) -> Result<Request, ParserError> {
             }
         }
         Token::Set => {
-            if argc != 3 {
+            if argc != 3 && argc != 5 {
                 return Err(ParserError(format!(
-                    "Set op expected 2 operands, got {}",
+                    "Set op expected 2 operands, or 2 operands plus EX <seconds>, got {}",
                     argc - 1
                 )));
             }
@@ -81,7 +192,143 @@ async fn parse_tokens(tokens: Vec<Token>) -> Result<Request, ParserError> {
                 Token::Operand(v) => val = v.to_string(),
                 _ => return Err(ParserError(format!("Set operands cannot be op types"))),
             }
-            return Ok(Request::Set { key: key, val: val });
+            let ex = if argc == 5 {
+                match (&tokens[3], &tokens[4]) {
+                    (Token::Ex, Token::Operand(secs)) => match secs.parse::<u64>() {
+                        Ok(n) => Some(n),
+                        Err(_) => {
+                            return Err(ParserError(format!(
+                                "EX expects an integer number of seconds"
+                            )))
+                        }
+                    },
+                    _ => {
+                        return Err(ParserError(format!(
+                            "Set expected EX <seconds> after key and value"
+                        )))
+                    }
+                }
+            } else {
+                None
+            };
+            return Ok(Request::Set {
+                key: key,
+                val: val,
+                ex: ex,
+            });
+        }
+        Token::Expire => {
+            if argc != 3 {
+                return Err(ParserError(format!(
+                    "Expire op expected exactly 2 operands, got {}",
+                    argc - 1
+                )));
+            }
+            let key;
+            match &tokens[1] {
+                Token::Operand(k) => key = k.to_string(),
+                _ => return Err(ParserError(format!("Expire operands cannot be op types"))),
+            }
+            match &tokens[2] {
+                Token::Operand(secs) => match secs.parse::<u64>() {
+                    Ok(n) => {
+                        return Ok(Request::Expire {
+                            key: key,
+                            seconds: n,
+                        })
+                    }
+                    Err(_) => {
+                        return Err(ParserError(format!(
+                            "Expire expects an integer number of seconds"
+                        )))
+                    }
+                },
+                _ => return Err(ParserError(format!("Expire operands cannot be op types"))),
+            }
+        }
+        Token::Ttl => {
+            if argc != 2 {
+                return Err(ParserError(format!(
+                    "Ttl op expected exactly 1 operand, got {}",
+                    argc - 1
+                )));
+            }
+            match &tokens[1] {
+                Token::Operand(k) => return Ok(Request::Ttl { key: k.to_string() }),
+                _ => return Err(ParserError(format!("Ttl operands cannot be op types"))),
+            }
+        }
+        Token::Auth => {
+            if argc != 2 {
+                return Err(ParserError(format!(
+                    "Auth op expected exactly 1 operand, got {}",
+                    argc - 1
+                )));
+            }
+            match &tokens[1] {
+                Token::Operand(s) => {
+                    return Ok(Request::Auth {
+                        secret: s.to_string(),
+                    });
+                }
+                _ => return Err(ParserError(format!("Auth operands cannot be op types"))),
+            }
+        }
+        Token::Subscribe => {
+            if argc != 2 {
+                return Err(ParserError(format!(
+                    "Subscribe op expected exactly 1 operand, got {}",
+                    argc - 1
+                )));
+            }
+            match &tokens[1] {
+                Token::Operand(c) => {
+                    return Ok(Request::Subscribe {
+                        channel: c.to_string(),
+                    });
+                }
+                _ => return Err(ParserError(format!("Subscribe operands cannot be op types"))),
+            }
+        }
+        Token::Unsubscribe => {
+            if argc != 2 {
+                return Err(ParserError(format!(
+                    "Unsubscribe op expected exactly 1 operand, got {}",
+                    argc - 1
+                )));
+            }
+            match &tokens[1] {
+                Token::Operand(c) => {
+                    return Ok(Request::Unsubscribe {
+                        channel: c.to_string(),
+                    });
+                }
+                _ => return Err(ParserError(format!("Unsubscribe operands cannot be op types"))),
+            }
+        }
+        Token::Publish => {
+            if argc < 3 {
+                return Err(ParserError(format!(
+                    "Publish op expected a channel and a message, got {}",
+                    argc - 1
+                )));
+            }
+            let channel;
+            match &tokens[1] {
+                Token::Operand(c) => channel = c.to_string(),
+                _ => return Err(ParserError(format!("Publish operands cannot be op types"))),
+            }
+            let mut words = Vec::new();
+            for t in &tokens[2..] {
+                match t {
+                    Token::Operand(w) => words.push(w.to_string()),
+                    _ => return Err(ParserError(format!("Publish operands cannot be op types"))),
+                }
+            }
+            return Ok(Request::Publish {
+                channel: channel,
+                message: words.join(" "),
+            });
         }
         _ => return Err(ParserError(format!("Invalid op token"))),
     }
@@ -90,15 +337,32 @@ async fn parse_tokens(tokens: Vec<Token>) -> Result<Request, ParserError> {
 async fn tokenize(bytes: &[u8]) -> Vec<Token> {
     let mut tokens = Vec::new();
     let text = std::str::from_utf8(bytes).unwrap();
-    let mut chunks = text
-        .split(|c: char| c.is_whitespace() || c == '\u{0}')
-        .filter(|s| !s.is_empty());
+    let mut chunks = text.split(char::is_whitespace).filter(|s| !s.is_empty());
+
+    // Everything after the PUBLISH op itself (channel and message alike) is
+    // free-form text, so it must not be re-matched against reserved
+    // keywords the way operands to other ops are.
+    let mut after_publish = false;
 
     while let Some(chunk) = chunks.next() {
+        if after_publish {
+            tokens.push(Token::Operand(chunk.to_string()));
+            continue;
+        }
         match chunk.to_uppercase().as_str() {
             "PING" => tokens.push(Token::Ping),
             "GET" => tokens.push(Token::Get),
             "SET" => tokens.push(Token::Set),
+            "EX" => tokens.push(Token::Ex),
+            "EXPIRE" => tokens.push(Token::Expire),
+            "TTL" => tokens.push(Token::Ttl),
+            "AUTH" => tokens.push(Token::Auth),
+            "SUBSCRIBE" => tokens.push(Token::Subscribe),
+            "UNSUBSCRIBE" => tokens.push(Token::Unsubscribe),
+            "PUBLISH" => {
+                tokens.push(Token::Publish);
+                after_publish = true;
+            }
             _ => tokens.push(Token::Operand(chunk.to_string())),
         }
     }
@@ -112,44 +376,179 @@ async fn parse_request(bytes: &[u8]) -> Result<Request, ParserError> {
     Ok(req)
 }
 
-async fn exec_request(req: Request, store: &mut HashStore<String, String>) -> Response {
+async fn exec_request<S: Store<String, String>>(req: Request, store: &mut S) -> Response {
     match req {
         Request::Ping => {
             return Response {
-                body: "PONG".to_string(),
+                body: "+PONG".to_string(),
             }
         }
         Request::Get { key } => match store.get(&key).unwrap() {
             Some(val) => {
                 return Response {
-                    body: format!("\"{}\"", val),
+                    body: format!("+\"{}\"", val),
                 }
             }
             None => {
                 return Response {
-                    body: "(nil)".to_string(),
+                    body: "+(nil)".to_string(),
                 }
             }
         },
-        Request::Set { key, val } => {
-            let _ = store.set(key, val);
+        Request::Set { key, val, ex } => {
+            match ex {
+                Some(secs) => {
+                    let _ = store.set_ex(key, val, std::time::Duration::from_secs(secs));
+                }
+                None => {
+                    let _ = store.set(key, val);
+                }
+            }
+            return Response {
+                body: "+OK".to_string(),
+            };
+        }
+        Request::Expire { key, seconds } => {
+            let existed = store.expire(&key, std::time::Duration::from_secs(seconds));
             return Response {
-                body: "OK".to_string(),
+                body: if existed { "+1".to_string() } else { "+0".to_string() },
             };
         }
+        Request::Ttl { key } => {
+            let body = match store.ttl(&key) {
+                Ttl::Missing => "+-2".to_string(),
+                Ttl::NoExpiry => "+-1".to_string(),
+                Ttl::Remaining(d) => format!("+{}", d.as_secs()),
+            };
+            return Response { body };
+        }
         Request::NoOp => {
             return Response {
-                body: "\u{0}".to_string(),
+                body: "+OK".to_string(),
             }
         }
         Request::Invalid { error } => {
             return Response {
-                body: format!("ERROR: {}", error),
+                body: format!("-ERR {}", error),
+            }
+        }
+        // AUTH is intercepted in the connection loop, and Subscribe/
+        // Unsubscribe/Publish are handled directly by the manager loop
+        // (which owns the subscribers map), before any of them reach here;
+        // these arms only exist so the match stays exhaustive.
+        Request::Auth { .. }
+        | Request::Subscribe { .. }
+        | Request::Unsubscribe { .. }
+        | Request::Publish { .. } => {
+            return Response {
+                body: "+OK".to_string(),
+            };
+        }
+    }
+}
+
+/// Scans the process argv for `--persist <path>`, returning the write-ahead
+/// log path to use when present. Without it the server keeps data in memory
+/// only.
+fn parse_persist_path() -> Option<std::path::PathBuf> {
+    kiva::argv::flag_value("--persist").map(std::path::PathBuf::from)
+}
+
+/// How often the manager actively sweeps the store for expired keys, on top
+/// of the lazy eviction `get` already does.
+const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Owns the data store and the pub/sub subscribers map, and drives both off
+/// the shared request queue. Generic over the storage backend so the
+/// server can pick in-memory or durable storage at startup without this
+/// loop changing.
+async fn run_manager<S: Store<String, String>>(mut rx: mpsc::Receiver<Message>, mut store: S) {
+    let mut subscribers: std::collections::HashMap<String, Vec<(u64, mpsc::Sender<String>)>> =
+        std::collections::HashMap::new();
+    let mut sweep = tokio::time::interval(SWEEP_INTERVAL);
+    println!("** Initialized data store");
+
+    loop {
+        tokio::select! {
+            msg = rx.recv() => {
+                let msg = match msg {
+                    Some(msg) => msg,
+                    None => break, // All senders dropped; nothing left to manage.
+                };
+                let conn_id = msg.conn_id;
+                let resp = match msg.req {
+                    Request::Subscribe { channel } => {
+                        let sender = msg
+                            .subscribe_tx
+                            .expect("Subscribe message is missing its subscription sender");
+                        subscribers
+                            .entry(channel)
+                            .or_insert_with(Vec::new)
+                            .push((conn_id, sender));
+                        Response {
+                            body: "+OK".to_string(),
+                        }
+                    }
+                    Request::Unsubscribe { channel } => {
+                        if let Some(subs) = subscribers.get_mut(&channel) {
+                            subs.retain(|(id, _)| *id != conn_id);
+                        }
+                        Response {
+                            body: "+OK".to_string(),
+                        }
+                    }
+                    Request::Publish { channel, message } => {
+                        let mut delivered = 0;
+                        if let Some(subs) = subscribers.get_mut(&channel) {
+                            let mut dead = Vec::new();
+                            for (id, sender) in subs.iter_mut() {
+                                // `try_send` rather than `send`: awaiting a
+                                // full mailbox would block this manager
+                                // task (and every other connection's
+                                // requests behind it) on one slow
+                                // subscriber's pace. A full mailbox just
+                                // drops the message for that subscriber.
+                                match sender.try_send(message.clone()) {
+                                    Ok(()) => delivered += 1,
+                                    Err(mpsc::error::TrySendError::Full(_)) => {}
+                                    Err(mpsc::error::TrySendError::Closed(_)) => dead.push(*id),
+                                }
+                            }
+                            subs.retain(|(id, _)| !dead.contains(id));
+                        }
+                        Response {
+                            body: format!("+{}", delivered),
+                        }
+                    }
+                    req => exec_request(req, &mut store).await,
+                };
+                let _ = msg.pipe.send(resp);
+            }
+            _ = sweep.tick() => {
+                let evicted = store.sweep_expired();
+                if evicted > 0 {
+                    println!("** Swept {} expired key(s)", evicted);
+                }
             }
         }
     }
 }
 
+/// Finds the index of the next `\r\n` line terminator in `buf`, if any.
+fn find_crlf(buf: &[u8]) -> Option<usize> {
+    buf.windows(2).position(|w| w == b"\r\n")
+}
+
+/// Pulls the next complete `\r\n`-terminated line out of `buf`, leaving any
+/// trailing partial line behind for the next read. Returns the line with the
+/// terminator stripped.
+fn next_line(buf: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let pos = find_crlf(buf)?;
+    let mut line: Vec<u8> = buf.drain(..pos + 2).collect();
+    line.truncate(pos);
+    Some(line)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("==================");
@@ -159,53 +558,146 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cbuf = 100;
     let (tx, mut rx) = mpsc::channel(cbuf);
 
-    let _manager = tokio::spawn(async move {
-        let mut store: HashStore<String, String> = Store::new();
-        println!("** Initialized data store");
+    let _manager = match parse_persist_path() {
+        Some(path) => {
+            let store: PersistentStore<String, String> = Store::with_path(&path)
+                .unwrap_or_else(|e| panic!("failed to open write-ahead log at {:?}: {}", path, e));
+            println!("** Persisting to write-ahead log at {:?}", path);
+            tokio::spawn(run_manager(rx, store))
+        }
+        None => {
+            let store: HashStore<String, String> = Store::new();
+            tokio::spawn(run_manager(rx, store))
+        }
+    };
+
+    let authenticator: Option<Arc<dyn Authenticator>> = parse_auth_secret()
+        .map(|secret| Arc::new(StaticSecretAuthenticator::new(secret)) as Arc<dyn Authenticator>);
+    if authenticator.is_some() {
+        println!("** AUTH required for data and pub/sub commands on every connection");
+    }
 
-        while let Some(msg) = rx.recv().await {
-            let msg: Message = msg; // Make type of `msg` explicit to compiler
-            let resp = exec_request(msg.req, &mut store).await;
-            let _ = msg.pipe.send(resp);
+    #[cfg(feature = "tls")]
+    let acceptor = parse_tls_args()
+        .map(|t| kiva::tls::load_server_acceptor(&t.cert, &t.key))
+        .transpose()?;
+    #[cfg(not(feature = "tls"))]
+    {
+        if parse_tls_args().is_some() {
+            panic!("--tls requires the `tls` feature to be enabled");
         }
-    });
+    }
 
     let url = "127.0.0.1:6464";
     let mut listener = TcpListener::bind(url).await?;
     println!("** Listening on: {}", url);
 
     loop {
-        let (mut socket, addr) = listener.accept().await?;
+        let (socket, addr) = listener.accept().await?;
         println!(
             "** Successfully established inbound TCP connection with: {}",
             &addr
         );
+
+        #[cfg(feature = "tls")]
+        let mut socket = match &acceptor {
+            Some(acceptor) => ServerConn::Tls(acceptor.accept(socket).await?),
+            None => ServerConn::Plain(socket),
+        };
+        #[cfg(not(feature = "tls"))]
+        let mut socket = ServerConn::Plain(socket);
+
         let mut txc = tx.clone();
+        let conn_auth = authenticator.clone();
+        let conn_id = NEXT_CONN_ID.fetch_add(1, Ordering::Relaxed);
         let _task = tokio::spawn(async move {
+            let mut buf = Vec::new();
+            let mut chunk = [0; 1024];
+            let mut authenticated = conn_auth.is_none();
+
+            // Every published message this connection is subscribed to
+            // arrives on `sub_rx`, regardless of which channel it came
+            // from; `sub_tx` is handed to the manager on each `SUBSCRIBE`.
+            let (sub_tx, mut sub_rx) = mpsc::channel::<String>(cbuf);
+
             loop {
-                let mut buf = [0; 128];
-                let _ = socket.read(&mut buf[..]).await;
-
-                let req;
-                match parse_request(&buf).await {
-                    Ok(request) => req = request,
-                    Err(e) => {
-                        req = Request::Invalid {
-                            error: e.0.to_string(),
-                        }
-                    }
-                }
+                tokio::select! {
+                    read_result = socket.read(&mut chunk[..]) => {
+                        let n = match read_result {
+                            Ok(0) => break, // EOF: peer closed the connection
+                            Ok(n) => n,
+                            Err(_) => break,
+                        };
+                        buf.extend_from_slice(&chunk[..n]);
 
-                let (send_pipe, recv_pipe) = oneshot::channel();
-                let msg = Message {
-                    req: req,
-                    pipe: send_pipe,
-                };
+                        while let Some(line) = next_line(&mut buf) {
+                            let req;
+                            match parse_request(&line).await {
+                                Ok(request) => req = request,
+                                Err(e) => {
+                                    req = Request::Invalid {
+                                        error: e.0.to_string(),
+                                    }
+                                }
+                            }
+
+                            let resp = match &req {
+                                Request::Auth { secret } => match &conn_auth {
+                                    Some(auth) if auth.verify(secret) => {
+                                        authenticated = true;
+                                        Response {
+                                            body: "+OK".to_string(),
+                                        }
+                                    }
+                                    Some(_) => Response {
+                                        body: "-ERR invalid secret".to_string(),
+                                    },
+                                    None => Response {
+                                        body: "+OK".to_string(),
+                                    },
+                                },
+                                r if requires_auth(r) && conn_auth.is_some() && !authenticated => {
+                                    Response {
+                                        body: "-ERR not authenticated".to_string(),
+                                    }
+                                }
+                                Request::Subscribe { .. } => {
+                                    let (send_pipe, recv_pipe) = oneshot::channel();
+                                    let msg = Message {
+                                        req: req,
+                                        pipe: send_pipe,
+                                        conn_id: conn_id,
+                                        subscribe_tx: Some(sub_tx.clone()),
+                                    };
 
-                let _ = txc.send(msg).await;
+                                    let _ = txc.send(msg).await;
+                                    recv_pipe.await.unwrap()
+                                }
+                                _ => {
+                                    let (send_pipe, recv_pipe) = oneshot::channel();
+                                    let msg = Message {
+                                        req: req,
+                                        pipe: send_pipe,
+                                        conn_id: conn_id,
+                                        subscribe_tx: None,
+                                    };
 
-                let resp = recv_pipe.await.unwrap();
-                let _ = socket.write_all(resp.body.as_bytes()).await;
+                                    let _ = txc.send(msg).await;
+                                    recv_pipe.await.unwrap()
+                                }
+                            };
+
+                            let _ = socket
+                                .write_all(format!("{}\r\n", resp.body).as_bytes())
+                                .await;
+                        }
+                    }
+                    Some(published) = sub_rx.recv() => {
+                        let _ = socket
+                            .write_all(format!("+{}\r\n", published).as_bytes())
+                            .await;
+                    }
+                }
             }
         });
     }
@@ -220,7 +712,7 @@ mod tests {
     async fn test_tokenize() {
         assert_eq!(tokenize(b"PING    ").await, vec![Token::Ping]);
         assert_eq!(
-            tokenize("SET foo bar\u{0}\u{0}\u{0}".as_bytes()).await,
+            tokenize(b"SET foo bar").await,
             vec![
                 Token::Set,
                 Token::Operand("foo".to_string()),
@@ -248,6 +740,53 @@ mod tests {
             ]
         );
         assert_eq!(tokenize(b" ").await, vec![]);
+        assert_eq!(
+            tokenize(b"AUTH hunter2").await,
+            vec![Token::Auth, Token::Operand("hunter2".to_string())]
+        );
+        assert_eq!(
+            tokenize(b"PUBLISH news hello world").await,
+            vec![
+                Token::Publish,
+                Token::Operand("news".to_string()),
+                Token::Operand("hello".to_string()),
+                Token::Operand("world".to_string()),
+            ]
+        );
+        assert_eq!(
+            tokenize(b"SET foo bar EX 60").await,
+            vec![
+                Token::Set,
+                Token::Operand("foo".to_string()),
+                Token::Operand("bar".to_string()),
+                Token::Ex,
+                Token::Operand("60".to_string()),
+            ]
+        );
+        assert_eq!(
+            tokenize(b"EXPIRE foo 60").await,
+            vec![
+                Token::Expire,
+                Token::Operand("foo".to_string()),
+                Token::Operand("60".to_string()),
+            ]
+        );
+        assert_eq!(
+            tokenize(b"TTL foo").await,
+            vec![Token::Ttl, Token::Operand("foo".to_string())]
+        );
+        assert_eq!(
+            tokenize(b"PUBLISH news tell user to GET help").await,
+            vec![
+                Token::Publish,
+                Token::Operand("news".to_string()),
+                Token::Operand("tell".to_string()),
+                Token::Operand("user".to_string()),
+                Token::Operand("to".to_string()),
+                Token::Operand("GET".to_string()),
+                Token::Operand("help".to_string()),
+            ]
+        );
     }
 
     #[tokio::test]
@@ -274,10 +813,78 @@ mod tests {
             .unwrap(),
             Request::Set {
                 key: "foo".to_string(),
-                val: "bar".to_string()
+                val: "bar".to_string(),
+                ex: None,
+            }
+        );
+        assert_eq!(
+            parse_tokens(vec![
+                Token::Set,
+                Token::Operand("foo".to_string()),
+                Token::Operand("bar".to_string()),
+                Token::Ex,
+                Token::Operand("60".to_string()),
+            ])
+            .await
+            .unwrap(),
+            Request::Set {
+                key: "foo".to_string(),
+                val: "bar".to_string(),
+                ex: Some(60),
+            }
+        );
+        assert_eq!(
+            parse_tokens(vec![
+                Token::Expire,
+                Token::Operand("foo".to_string()),
+                Token::Operand("60".to_string()),
+            ])
+            .await
+            .unwrap(),
+            Request::Expire {
+                key: "foo".to_string(),
+                seconds: 60,
+            }
+        );
+        assert_eq!(
+            parse_tokens(vec![Token::Ttl, Token::Operand("foo".to_string())])
+                .await
+                .unwrap(),
+            Request::Ttl {
+                key: "foo".to_string()
             }
         );
         assert_eq!(parse_tokens(vec![]).await.unwrap(), Request::NoOp);
+        assert_eq!(
+            parse_tokens(vec![Token::Auth, Token::Operand("hunter2".to_string())])
+                .await
+                .unwrap(),
+            Request::Auth {
+                secret: "hunter2".to_string()
+            }
+        );
+        assert_eq!(
+            parse_tokens(vec![Token::Subscribe, Token::Operand("news".to_string())])
+                .await
+                .unwrap(),
+            Request::Subscribe {
+                channel: "news".to_string()
+            }
+        );
+        assert_eq!(
+            parse_tokens(vec![
+                Token::Publish,
+                Token::Operand("news".to_string()),
+                Token::Operand("hello".to_string()),
+                Token::Operand("world".to_string()),
+            ])
+            .await
+            .unwrap(),
+            Request::Publish {
+                channel: "news".to_string(),
+                message: "hello world".to_string()
+            }
+        );
     }
 
     #[tokio::test]
@@ -313,8 +920,66 @@ mod tests {
         assert_eq!(
             exec_request(Request::Ping, &mut store).await,
             Response {
-                body: "PONG".to_string()
+                body: "+PONG".to_string()
             }
         )
     }
+
+    #[test]
+    fn test_auth_debug_redacts_secret() {
+        let debug = format!(
+            "{:?}",
+            Request::Auth {
+                secret: "hunter2".to_string()
+            }
+        );
+        assert!(!debug.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_requires_auth() {
+        assert!(requires_auth(&Request::Get {
+            key: "k".to_string()
+        }));
+        assert!(requires_auth(&Request::Set {
+            key: "k".to_string(),
+            val: "v".to_string(),
+            ex: None,
+        }));
+        assert!(requires_auth(&Request::Expire {
+            key: "k".to_string(),
+            seconds: 60,
+        }));
+        assert!(requires_auth(&Request::Ttl {
+            key: "k".to_string()
+        }));
+        assert!(requires_auth(&Request::Subscribe {
+            channel: "c".to_string()
+        }));
+        assert!(requires_auth(&Request::Unsubscribe {
+            channel: "c".to_string()
+        }));
+        assert!(requires_auth(&Request::Publish {
+            channel: "c".to_string(),
+            message: "m".to_string(),
+        }));
+
+        assert!(!requires_auth(&Request::Ping));
+        assert!(!requires_auth(&Request::Auth {
+            secret: "s".to_string()
+        }));
+        assert!(!requires_auth(&Request::NoOp));
+        assert!(!requires_auth(&Request::Invalid {
+            error: "e".to_string()
+        }));
+    }
+
+    #[test]
+    fn test_next_line() {
+        let mut buf = b"PING\r\nGET foo\r\nSET ba".to_vec();
+        assert_eq!(next_line(&mut buf), Some(b"PING".to_vec()));
+        assert_eq!(next_line(&mut buf), Some(b"GET foo".to_vec()));
+        assert_eq!(next_line(&mut buf), None);
+        assert_eq!(buf, b"SET ba".to_vec());
+    }
 }
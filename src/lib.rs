@@ -0,0 +1,6 @@
+pub mod argv;
+pub mod auth;
+pub mod store;
+pub mod tls;
+
+pub use store::{HashStore, PersistentStore, Store, Ttl};
@@ -1,19 +1,89 @@
 use std::cmp::Eq;
 use std::collections::HashMap;
 use std::fmt;
+use std::fs::{File, OpenOptions};
 use std::hash::Hash;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 type Result<T> = std::result::Result<Option<T>, Box<dyn std::error::Error>>;
 
+/// Remaining time-to-live for a key, as reported by `Store::ttl`.
+#[derive(Debug, PartialEq)]
+pub enum Ttl {
+    /// The key does not exist (or has already expired).
+    Missing,
+    /// The key exists but carries no expiry.
+    NoExpiry,
+    /// The key exists and expires in the given duration from now.
+    Remaining(Duration),
+}
+
 pub trait Store<K, V> {
     fn new() -> Self;
+
+    /// Constructs a store backed by on-disk state at `path`, rebuilding
+    /// in-memory state from whatever is already there. Backends with no
+    /// durable state can ignore `path` and fall back to `new()`.
+    fn with_path(_path: &Path) -> std::result::Result<Self, Box<dyn std::error::Error>>
+    where
+        Self: Sized,
+    {
+        Ok(Self::new())
+    }
+
     fn set(&mut self, key: K, val: V) -> Result<V>;
     fn get(&self, key: &K) -> Result<&V>;
+
+    /// Like `set`, but `key` expires `ttl` from now. Backends without TTL
+    /// support can ignore `ttl` and just fall back to `set`.
+    fn set_ex(&mut self, key: K, val: V, _ttl: Duration) -> Result<V> {
+        self.set(key, val)
+    }
+
+    /// Sets an expiry of `ttl` from now on an already-present key. Returns
+    /// `false` if the key does not exist.
+    ///
+    /// Backends without TTL support can't actually set the expiry, but they
+    /// should still report whether `key` exists rather than always saying
+    /// it doesn't.
+    fn expire(&mut self, key: &K, _ttl: Duration) -> bool {
+        matches!(self.get(key), Ok(Some(_)))
+    }
+
+    /// Reports the remaining time-to-live for `key`.
+    fn ttl(&self, key: &K) -> Ttl {
+        match self.get(key) {
+            Ok(Some(_)) => Ttl::NoExpiry,
+            _ => Ttl::Missing,
+        }
+    }
+
+    /// Actively evicts expired keys, returning how many were removed.
+    /// Backends without TTL support have nothing to sweep.
+    fn sweep_expired(&mut self) -> usize {
+        0
+    }
+}
+
+/// A value plus the instant it should be considered gone, if any.
+#[derive(Debug)]
+struct Entry<V> {
+    val: V,
+    expires_at: Option<Instant>,
+}
+
+impl<V> Entry<V> {
+    fn is_expired(&self) -> bool {
+        matches!(self.expires_at, Some(deadline) if deadline <= Instant::now())
+    }
 }
 
 #[derive(Debug)]
 pub struct HashStore<K, V> {
-    store: HashMap<K, V>,
+    store: HashMap<K, Entry<V>>,
 }
 
 impl<K, V> Store<K, V> for HashStore<K, V>
@@ -27,20 +97,171 @@ where
     }
 
     fn get(&self, key: &K) -> Result<&V> {
-        // If some constraints are not fulfilled, return an error
         match self.store.get(&key) {
-            Some(val) => Ok(Some(val)),
+            // An expired entry is lazily treated as absent; it's actually
+            // reclaimed by `sweep_expired` or overwritten by the next
+            // `set`/`set_ex` of the same key.
+            Some(entry) if entry.is_expired() => Ok(None),
+            Some(entry) => Ok(Some(&entry.val)),
             None => Ok(None),
         }
     }
 
     fn set(&mut self, key: K, val: V) -> Result<V> {
-        // If some constraints are not fulfilled, return an error
-        match self.store.insert(key, val) {
+        let prev = self.store.insert(
+            key,
+            Entry {
+                val,
+                expires_at: None,
+            },
+        );
+        Ok(prev.and_then(|e| if e.is_expired() { None } else { Some(e.val) }))
+    }
+
+    fn set_ex(&mut self, key: K, val: V, ttl: Duration) -> Result<V> {
+        let prev = self.store.insert(
+            key,
+            Entry {
+                val,
+                expires_at: Some(Instant::now() + ttl),
+            },
+        );
+        Ok(prev.and_then(|e| if e.is_expired() { None } else { Some(e.val) }))
+    }
+
+    fn expire(&mut self, key: &K, ttl: Duration) -> bool {
+        match self.store.get_mut(key) {
+            Some(entry) if !entry.is_expired() => {
+                entry.expires_at = Some(Instant::now() + ttl);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn ttl(&self, key: &K) -> Ttl {
+        match self.store.get(key) {
+            Some(entry) if entry.is_expired() => Ttl::Missing,
+            Some(Entry {
+                expires_at: None, ..
+            }) => Ttl::NoExpiry,
+            Some(Entry {
+                expires_at: Some(deadline),
+                ..
+            }) => Ttl::Remaining(deadline.saturating_duration_since(Instant::now())),
+            None => Ttl::Missing,
+        }
+    }
+
+    fn sweep_expired(&mut self) -> usize {
+        let before = self.store.len();
+        self.store.retain(|_, entry| !entry.is_expired());
+        before - self.store.len()
+    }
+}
+
+/// Number of `set` calls between log compactions, i.e. rewrites of the
+/// write-ahead log from the current in-memory state.
+const COMPACTION_THRESHOLD: usize = 1000;
+
+/// A `Store` that durably persists every write to an append-only,
+/// line-delimited write-ahead log, replaying it on startup to rebuild state.
+///
+/// Each line in the log is a `SET key val` record. Once
+/// `COMPACTION_THRESHOLD` writes have accumulated since the last rewrite,
+/// the log is replaced with a fresh one built from the current map, so its
+/// size stays bounded by the number of live keys rather than the number of
+/// writes ever made.
+pub struct PersistentStore<K, V> {
+    store: HashMap<K, V>,
+    log: File,
+    path: PathBuf,
+    writes_since_compaction: usize,
+}
+
+impl<K, V> PersistentStore<K, V>
+where
+    K: Eq + Hash + Clone + fmt::Display + FromStr,
+    V: Clone + fmt::Display + FromStr,
+{
+    fn replay(path: &Path) -> std::io::Result<HashMap<K, V>> {
+        let mut store = HashMap::new();
+        if !path.exists() {
+            return Ok(store);
+        }
+
+        let file = File::open(path)?;
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let mut parts = line.splitn(3, ' ');
+            if let (Some("SET"), Some(k), Some(v)) = (parts.next(), parts.next(), parts.next()) {
+                if let (Ok(key), Ok(val)) = (k.parse::<K>(), v.parse::<V>()) {
+                    store.insert(key, val);
+                }
+            }
+        }
+        Ok(store)
+    }
+
+    /// Rewrites the log from the current map and reopens it for appending,
+    /// dropping every superseded `SET` record accumulated so far.
+    fn compact(&mut self) -> std::io::Result<()> {
+        let mut tmp_path = self.path.clone();
+        tmp_path.set_extension("tmp");
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            for (key, val) in self.store.iter() {
+                writeln!(tmp, "SET {} {}", key, val)?;
+            }
+        }
+        std::fs::rename(&tmp_path, &self.path)?;
+
+        self.log = OpenOptions::new().append(true).open(&self.path)?;
+        self.writes_since_compaction = 0;
+        Ok(())
+    }
+}
+
+impl<K, V> Store<K, V> for PersistentStore<K, V>
+where
+    K: Eq + Hash + Clone + fmt::Display + FromStr,
+    V: Clone + fmt::Display + FromStr,
+{
+    fn new() -> Self {
+        Self::with_path(Path::new("kiba.wal")).expect("failed to open default write-ahead log")
+    }
+
+    fn with_path(path: &Path) -> std::result::Result<Self, Box<dyn std::error::Error>> {
+        let store = Self::replay(path)?;
+        let log = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(PersistentStore {
+            store,
+            log,
+            path: path.to_path_buf(),
+            writes_since_compaction: 0,
+        })
+    }
+
+    fn get(&self, key: &K) -> Result<&V> {
+        match self.store.get(key) {
             Some(val) => Ok(Some(val)),
             None => Ok(None),
         }
     }
+
+    fn set(&mut self, key: K, val: V) -> Result<V> {
+        writeln!(self.log, "SET {} {}", key, val)?;
+        self.log.flush()?;
+        self.writes_since_compaction += 1;
+
+        let prev = self.store.insert(key, val);
+
+        if self.writes_since_compaction >= COMPACTION_THRESHOLD {
+            self.compact()?;
+        }
+
+        Ok(prev)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -65,4 +286,62 @@ mod tests {
         assert_eq!(store.set("baz".to_string(), 7).unwrap(), None);
         assert_eq!(store.set("foo".to_string(), 8).unwrap(), Some(5));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_hash_store_expiry() {
+        let mut store: HashStore<String, u32> = Store::new();
+        assert_eq!(store.ttl(&"foo".to_string()), Ttl::Missing);
+
+        let _ = store.set("foo".to_string(), 1);
+        assert_eq!(store.ttl(&"foo".to_string()), Ttl::NoExpiry);
+
+        assert!(store.expire(&"foo".to_string(), Duration::from_secs(60)));
+        match store.ttl(&"foo".to_string()) {
+            Ttl::Remaining(d) => assert!(d <= Duration::from_secs(60)),
+            other => panic!("expected Remaining, got {:?}", other),
+        }
+
+        let _ = store.set_ex("bar".to_string(), 2, Duration::from_secs(0));
+        assert_eq!(store.get(&"bar".to_string()).unwrap(), None);
+        assert_eq!(store.ttl(&"bar".to_string()), Ttl::Missing);
+        assert_eq!(store.sweep_expired(), 1);
+    }
+
+    #[test]
+    fn test_persistent_store_replays_log_on_restart() {
+        let path = std::env::temp_dir().join(format!("kiba-test-{:?}.wal", std::thread::current().id()));
+        let _ = std::fs::remove_file(&path);
+
+        {
+            let mut store: PersistentStore<String, String> = Store::with_path(&path).unwrap();
+            let _ = store.set("foo".to_string(), "bar".to_string());
+            let _ = store.set("baz".to_string(), "qux".to_string());
+        }
+
+        let store: PersistentStore<String, String> = Store::with_path(&path).unwrap();
+        assert_eq!(store.get(&"foo".to_string()).unwrap(), Some(&"bar".to_string()));
+        assert_eq!(store.get(&"baz".to_string()).unwrap(), Some(&"qux".to_string()));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_persistent_store_expire_reports_existing_key() {
+        let path = std::env::temp_dir().join(format!(
+            "kiba-test-expire-{:?}.wal",
+            std::thread::current().id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let mut store: PersistentStore<String, String> = Store::with_path(&path).unwrap();
+        let _ = store.set("foo".to_string(), "bar".to_string());
+
+        // PersistentStore doesn't override `expire`, but the default impl
+        // should still distinguish "key exists, backend can't set a TTL"
+        // from "key doesn't exist" rather than collapsing both to false.
+        assert!(store.expire(&"foo".to_string(), Duration::from_secs(60)));
+        assert!(!store.expire(&"missing".to_string(), Duration::from_secs(60)));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,157 @@
+//! Optional TLS support for server and client connections, enabled with the
+//! `tls` feature. Both sides wrap a `TcpStream` in a small enum so the rest
+//! of the request-handling code can keep talking to "a stream" without
+//! caring whether `--tls` was passed.
+
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+#[cfg(feature = "tls")]
+use tokio_rustls::rustls::{Certificate, ClientConfig, NoClientAuth, PrivateKey, ServerConfig};
+#[cfg(feature = "tls")]
+use tokio_rustls::webpki::DNSNameRef;
+#[cfg(feature = "tls")]
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// Either a plaintext server-side connection or one wrapped in TLS.
+pub enum ServerConn {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(tokio_rustls::server::TlsStream<TcpStream>),
+}
+
+impl AsyncRead for ServerConn {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ServerConn::Plain(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            ServerConn::Tls(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ServerConn {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ServerConn::Plain(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            ServerConn::Tls(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ServerConn::Plain(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            ServerConn::Tls(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ServerConn::Plain(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(feature = "tls")]
+            ServerConn::Tls(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Either a plaintext client-side connection or one wrapped in TLS.
+pub enum ClientConn {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(tokio_rustls::client::TlsStream<TcpStream>),
+}
+
+impl AsyncRead for ClientConn {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ClientConn::Plain(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            ClientConn::Tls(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ClientConn {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ClientConn::Plain(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            ClientConn::Tls(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ClientConn::Plain(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            ClientConn::Tls(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ClientConn::Plain(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(feature = "tls")]
+            ClientConn::Tls(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Builds a `TlsAcceptor` from a PEM certificate chain and PKCS#8 private key on disk.
+#[cfg(feature = "tls")]
+pub fn load_server_acceptor(cert_path: &Path, key_path: &Path) -> io::Result<TlsAcceptor> {
+    let certs = load_certs(cert_path)?;
+    let mut keys = load_keys(key_path)?;
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config
+        .set_single_cert(certs, keys.remove(0))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Builds a `TlsConnector` that validates the server's certificate against
+/// the Mozilla root set bundled in `webpki_roots`, plus the `DNSNameRef` to
+/// validate it for.
+#[cfg(feature = "tls")]
+pub fn load_client_connector(server_name: &str) -> io::Result<(TlsConnector, DNSNameRef<'_>)> {
+    let mut config = ClientConfig::new();
+    config
+        .root_store
+        .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+    let name = DNSNameRef::try_from_ascii_str(server_name)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid server name"))?;
+    Ok((TlsConnector::from(Arc::new(config)), name))
+}
+
+#[cfg(feature = "tls")]
+fn load_certs(path: &Path) -> io::Result<Vec<Certificate>> {
+    let file = std::fs::File::open(path)?;
+    let mut reader = std::io::BufReader::new(file);
+    rustls_pemfile::certs(&mut reader)
+        .map(|certs| certs.into_iter().map(Certificate).collect())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid certificate"))
+}
+
+#[cfg(feature = "tls")]
+fn load_keys(path: &Path) -> io::Result<Vec<PrivateKey>> {
+    let file = std::fs::File::open(path)?;
+    let mut reader = std::io::BufReader::new(file);
+    let keys: Vec<PrivateKey> = rustls_pemfile::pkcs8_private_keys(&mut reader)
+        .map(|keys| keys.into_iter().map(PrivateKey).collect())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid private key"))?;
+    if keys.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no PKCS#8 private keys found in key file",
+        ));
+    }
+    Ok(keys)
+}